@@ -0,0 +1,412 @@
+// Supervises the shipagent-core Python sidecar: spawns it, relays its
+// stdout/stderr to the frontend as `backend-log` events, and transparently
+// restarts it on crash with exponential backoff so a flaky backend doesn't
+// take the whole app down with it.
+//
+// Startup is bounded by `STARTUP_TIMEOUT` rather than waiting forever for
+// the readiness line. Newer backends print `SHIPAGENT_READY={json}` with a
+// port plus optional version/pid/model; older ones only print
+// `SHIPAGENT_PORT=<port>`, which is still accepted for compatibility.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const STABLE_UPTIME: Duration = Duration::from_secs(10);
+const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// A single line of backend output, relayed to the frontend as a
+/// `backend-log` event so it can render a live console.
+#[derive(Clone, serde::Serialize)]
+struct LogLine {
+    stream: &'static str,
+    text: String,
+    ts: u128,
+}
+
+/// Readiness payload reported by the backend once it's listening. Modern
+/// builds print a structured `SHIPAGENT_READY={json}` line; older ones only
+/// print `SHIPAGENT_PORT=<port>`, which we still accept for compatibility.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct BackendInfo {
+    pub port: u16,
+    pub version: Option<String>,
+    pub pid: Option<u32>,
+    pub model: Option<String>,
+}
+
+/// Snapshot of the supervised backend, returned by `get_backend_status`.
+#[derive(Clone, Default, serde::Serialize)]
+pub struct BackendStatus {
+    running: bool,
+    port: Option<u16>,
+    restarts: u32,
+    last_error: Option<String>,
+}
+
+/// Managed state shared between the supervisor task and the Tauri commands.
+pub struct BackendState {
+    child: Mutex<Option<CommandChild>>,
+    status: Mutex<BackendStatus>,
+    /// Set by `stop_backend` so the supervisor knows the next `Terminated`
+    /// event is an intentional shutdown rather than a crash to restart from.
+    stopping: AtomicBool,
+    /// True for as long as the supervisor task behind the current
+    /// `start_sidecar` call is running — including the backoff window
+    /// between a crash and its respawn, where `status.running` is
+    /// momentarily `false` even though the backend isn't actually gone.
+    /// `stop_backend` must gate on this, not on `status.running`.
+    supervisor_alive: AtomicBool,
+    /// Signalled by the supervisor once it has observed the shutdown, so
+    /// `stop_backend` knows whether to wait or fall back to a hard kill.
+    stop_ack: Mutex<Option<tokio::sync::oneshot::Sender<Option<i32>>>>,
+    /// Serializes concurrent `stop_backend` calls (e.g. a manual quit
+    /// racing `install_update`'s internal shutdown) so they can't clobber
+    /// each other's `stop_ack` sender.
+    stop_lock: tokio::sync::Mutex<()>,
+    /// Wakes the supervisor immediately when `stop_backend` sets `stopping`,
+    /// so a request isn't left sitting unnoticed through the backoff sleep
+    /// or a mid-flight respawn.
+    stop_notify: tokio::sync::Notify,
+}
+
+impl Default for BackendState {
+    fn default() -> Self {
+        Self {
+            child: Mutex::new(None),
+            status: Mutex::new(BackendStatus::default()),
+            stopping: AtomicBool::new(false),
+            supervisor_alive: AtomicBool::new(false),
+            stop_ack: Mutex::new(None),
+            stop_lock: tokio::sync::Mutex::new(()),
+            stop_notify: tokio::sync::Notify::new(),
+        }
+    }
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+fn resource_path(app: &AppHandle) -> Result<PathBuf, String> {
+    // Tauri copies the one-folder PyInstaller build to Resources/backend-dist/
+    // at bundle time; resolve the executable inside it at runtime.
+    let path = app
+        .path()
+        .resource_dir()
+        .map_err(|e| format!("Failed to resolve resource dir: {e}"))?
+        .join("backend-dist")
+        .join("shipagent-core");
+
+    if !path.exists() {
+        return Err(format!("Backend binary not found at: {}", path.display()));
+    }
+
+    Ok(path)
+}
+
+/// Spawn the sidecar and block until it reports readiness, errors, exits
+/// early, or exceeds `STARTUP_TIMEOUT`. Port 0 tells uvicorn to bind to an
+/// OS-assigned port.
+async fn spawn_backend(
+    app: &AppHandle,
+) -> Result<(BackendInfo, CommandChild, tokio::sync::mpsc::Receiver<CommandEvent>), String> {
+    let path = resource_path(app)?;
+    let shell = app.shell();
+
+    let (mut rx, mut child) = shell
+        .command(path.to_str().unwrap())
+        .args(["serve", "--port", "0"])
+        .spawn()
+        .map_err(|e| format!("Failed to spawn backend: {e}"))?;
+
+    let discover = async {
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(line) => {
+                    let text = String::from_utf8_lossy(&line);
+                    let text = text.trim();
+                    if let Some(json) = text.strip_prefix("SHIPAGENT_READY=") {
+                        if let Ok(info) = serde_json::from_str::<BackendInfo>(json) {
+                            return Ok(info);
+                        }
+                    } else if let Some(p) = text.strip_prefix("SHIPAGENT_PORT=") {
+                        if let Ok(port) = p.trim().parse() {
+                            return Ok(BackendInfo {
+                                port,
+                                ..Default::default()
+                            });
+                        }
+                    }
+                }
+                CommandEvent::Error(e) => return Err(format!("Backend stderr: {e}")),
+                CommandEvent::Terminated(payload) => {
+                    return Err(format!("Backend exited early: {:?}", payload.code))
+                }
+                _ => {}
+            }
+        }
+        Err("Backend did not report readiness".to_string())
+    };
+
+    let info = match tokio::time::timeout(STARTUP_TIMEOUT, discover).await {
+        Ok(result) => result?,
+        Err(_) => {
+            let _ = child.kill();
+            return Err(format!(
+                "Backend did not become ready within {}s",
+                STARTUP_TIMEOUT.as_secs()
+            ));
+        }
+    };
+
+    Ok((info, child, rx))
+}
+
+fn emit_log(app: &AppHandle, stream: &'static str, line: &[u8]) {
+    let _ = app.emit(
+        "backend-log",
+        LogLine {
+            stream,
+            text: String::from_utf8_lossy(line).into_owned(),
+            ts: now_ms(),
+        },
+    );
+}
+
+fn update_status(app: &AppHandle, f: impl FnOnce(&mut BackendStatus)) {
+    let state = app.state::<BackendState>();
+    let mut status = state.status.lock().unwrap();
+    f(&mut status);
+}
+
+/// Mark the supervisor as gone and unblock whatever `stop_backend` call is
+/// waiting on `stop_ack`. Called from every exit path of `supervise`'s loop
+/// that observes a stop request, however far into that generation it is.
+fn finish_stop(app: &AppHandle, exit_code: Option<i32>) {
+    let state = app.state::<BackendState>();
+    state.supervisor_alive.store(false, Ordering::SeqCst);
+    update_status(app, |s| s.running = false);
+    let _ = app.emit("backend-stopped", exit_code);
+    if let Some(tx) = state.stop_ack.lock().unwrap().take() {
+        let _ = tx.send(exit_code);
+    }
+}
+
+/// Keep draining one generation of the sidecar's output, then, on exit,
+/// respawn it with exponential backoff until it either stabilizes or
+/// exceeds `MAX_CONSECUTIVE_FAILURES`. A stop request is honored as soon as
+/// it's observed — mid-drain, mid-backoff, or right after a respawn — not
+/// just at the moment a generation happens to terminate on its own.
+fn supervise(app: AppHandle, initial_rx: tokio::sync::mpsc::Receiver<CommandEvent>) {
+    tauri::async_runtime::spawn(async move {
+        let mut rx = initial_rx;
+        let mut backoff = INITIAL_BACKOFF;
+        let mut consecutive_failures: u32 = 0;
+        let mut started_at = Instant::now();
+
+        loop {
+            // Drain this generation's output until it terminates.
+            let mut exit_code: Option<i32> = None;
+            let mut terminated = false;
+            loop {
+                match rx.recv().await {
+                    Some(CommandEvent::Stdout(line)) => emit_log(&app, "stdout", &line),
+                    Some(CommandEvent::Stderr(line)) => emit_log(&app, "stderr", &line),
+                    Some(CommandEvent::Terminated(payload)) => {
+                        exit_code = payload.code;
+                        terminated = true;
+                        break;
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            update_status(&app, |s| s.running = false);
+
+            let state = app.state::<BackendState>();
+            if state.stopping.swap(false, Ordering::SeqCst) {
+                finish_stop(&app, exit_code);
+                return;
+            }
+            // A failed respawn attempt hands this loop an already-closed
+            // channel (see the `Err` arm below), so it ends up here having
+            // never actually drained a real process. Don't report that as
+            // a crash — there was nothing to crash.
+            if terminated {
+                let _ = app.emit("backend-exited", exit_code);
+            }
+
+            // Measured from the previous spawn's actual readiness (reset below
+            // on every respawn attempt, successful or not), not from when this
+            // attempt began —
+            // otherwise a slow handshake (e.g. a model-loading backend) would
+            // count toward "stable" uptime and mask real crash-looping.
+            if started_at.elapsed() >= STABLE_UPTIME {
+                backoff = INITIAL_BACKOFF;
+                consecutive_failures = 0;
+            }
+            consecutive_failures += 1;
+
+            if consecutive_failures > MAX_CONSECUTIVE_FAILURES {
+                let failures = consecutive_failures;
+                update_status(&app, |s| {
+                    s.last_error = Some(format!(
+                        "Backend crashed {failures} times in a row; giving up"
+                    ))
+                });
+                state.supervisor_alive.store(false, Ordering::SeqCst);
+                let _ = app.emit("backend-failed", consecutive_failures);
+                break;
+            }
+
+            // Wake immediately on a stop request instead of sleeping out the
+            // full backoff window unaware one came in.
+            // A permit left over from an earlier, already-handled stop is
+            // ignored unless `stopping` is actually set.
+            let sleep = tokio::time::sleep(backoff);
+            tokio::pin!(sleep);
+            loop {
+                tokio::select! {
+                    _ = &mut sleep => break,
+                    _ = state.stop_notify.notified() => {
+                        if state.stopping.swap(false, Ordering::SeqCst) {
+                            finish_stop(&app, None);
+                            return;
+                        }
+                    }
+                }
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+
+            match spawn_backend(&app).await {
+                Ok((info, mut child, new_rx)) => {
+                    // A stop could have been requested while we were
+                    // mid-handshake on this respawn; don't adopt a fresh
+                    // child the caller already believes is torn down.
+                    if state.stopping.swap(false, Ordering::SeqCst) {
+                        let _ = child.kill();
+                        finish_stop(&app, None);
+                        return;
+                    }
+                    started_at = Instant::now();
+                    rx = new_rx;
+                    *app.state::<BackendState>().child.lock().unwrap() = Some(child);
+                    update_status(&app, |s| {
+                        s.running = true;
+                        s.port = Some(info.port);
+                        s.restarts += 1;
+                        s.last_error = None;
+                    });
+                    let _ = app.emit("backend-port-changed", info.port);
+                }
+                Err(e) => {
+                    // A failed handshake is not uptime.
+                    started_at = Instant::now();
+                    update_status(&app, |s| s.last_error = Some(e));
+                    if state.stopping.swap(false, Ordering::SeqCst) {
+                        finish_stop(&app, None);
+                        return;
+                    }
+                    // No process to drain until the next attempt — hand the
+                    // loop a closed channel so it falls straight through to
+                    // the next backoff/retry instead of spinning.
+                    let (_tx, empty_rx) = tokio::sync::mpsc::channel(1);
+                    rx = empty_rx;
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn start_sidecar(
+    app: AppHandle,
+    state: State<'_, BackendState>,
+) -> Result<BackendInfo, String> {
+    let (info, child, rx) = spawn_backend(&app).await?;
+
+    state.stopping.store(false, Ordering::SeqCst);
+    state.supervisor_alive.store(true, Ordering::SeqCst);
+    *state.child.lock().unwrap() = Some(child);
+    *state.status.lock().unwrap() = BackendStatus {
+        running: true,
+        port: Some(info.port),
+        restarts: 0,
+        last_error: None,
+    };
+
+    supervise(app.clone(), rx);
+
+    Ok(info)
+}
+
+/// Write a newline-delimited line to the sidecar's stdin, enabling a
+/// line-oriented command protocol on top of the HTTP port.
+#[tauri::command]
+pub async fn send_to_backend(line: String, state: State<'_, BackendState>) -> Result<(), String> {
+    let mut guard = state.child.lock().unwrap();
+    let child = guard.as_mut().ok_or("Backend is not running")?;
+    child
+        .write(format!("{line}\n").as_bytes())
+        .map_err(|e| format!("Failed to write to backend stdin: {e}"))
+}
+
+/// Current supervised-backend snapshot, for the frontend to poll or render
+/// a status indicator from.
+#[tauri::command]
+pub fn get_backend_status(state: State<'_, BackendState>) -> BackendStatus {
+    state.status.lock().unwrap().clone()
+}
+
+/// Ask the backend to shut down cleanly instead of relying solely on
+/// process-kill-on-parent-exit: write a `SHUTDOWN` sentinel to its stdin and
+/// give it `SHUTDOWN_GRACE` to exit on its own before falling back to
+/// `child.kill()`. Lets ShipAgent flush pending writes/saves on window close.
+///
+/// Calls are serialized on `stop_lock` so a manual quit racing
+/// `install_update`'s internal shutdown can't clobber each other's
+/// `stop_ack` sender; the second caller simply observes the first one's
+/// result once `supervisor_alive` goes false.
+#[tauri::command]
+pub async fn stop_backend(state: State<'_, BackendState>) -> Result<(), String> {
+    let _guard = state.stop_lock.lock().await;
+
+    if !state.supervisor_alive.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let (tx, ack) = tokio::sync::oneshot::channel();
+    *state.stop_ack.lock().unwrap() = Some(tx);
+    state.stopping.store(true, Ordering::SeqCst);
+    // Wake the supervisor immediately in case it's asleep in the backoff
+    // window or mid-respawn rather than actively draining a running process.
+    state.stop_notify.notify_one();
+
+    if let Some(child) = state.child.lock().unwrap().as_mut() {
+        let _ = child.write(b"SHUTDOWN\n");
+    }
+
+    match tokio::time::timeout(SHUTDOWN_GRACE, ack).await {
+        Ok(Ok(_exit_code)) => {}
+        Ok(Err(_recv_error)) | Err(_elapsed) => {
+            if let Some(child) = state.child.lock().unwrap().take() {
+                let _ = child.kill();
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+// Drives tauri-plugin-updater explicitly instead of relying on its silent
+// built-in dialog, so the frontend can show real check/download/install
+// progress and coordinate it with shutting down the Python sidecar first.
+
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_updater::UpdaterExt;
+
+use crate::backend::BackendState;
+
+/// Result of `check_for_update`.
+#[derive(serde::Serialize)]
+pub struct UpdateInfo {
+    available: bool,
+    version: Option<String>,
+    notes: Option<String>,
+}
+
+/// Progress of an in-flight `install_update` download, emitted as
+/// `update-progress` events.
+#[derive(Clone, serde::Serialize)]
+struct UpdateProgress {
+    downloaded: usize,
+    total: Option<u64>,
+}
+
+#[tauri::command]
+pub async fn check_for_update(app: AppHandle) -> Result<UpdateInfo, String> {
+    let updater = app
+        .updater()
+        .map_err(|e| format!("Updater unavailable: {e}"))?;
+
+    let update = updater
+        .check()
+        .await
+        .map_err(|e| format!("Failed to check for update: {e}"))?;
+
+    Ok(match update {
+        Some(update) => UpdateInfo {
+            available: true,
+            version: Some(update.version),
+            notes: update.body,
+        },
+        None => UpdateInfo {
+            available: false,
+            version: None,
+            notes: None,
+        },
+    })
+}
+
+/// Download and install the pending update, cleanly stopping the Python
+/// sidecar first so it isn't killed mid-write. Emits `update-progress` as
+/// chunks download and `update-ready` once installed, so the frontend can
+/// prompt the user to relaunch.
+///
+/// `stop_backend` doesn't return until the supervisor has actually torn
+/// down — including a backend that was mid-crash-loop at the moment of the
+/// call — or forced a kill, so it's safe to move straight into
+/// `download_and_install` once it resolves.
+#[tauri::command]
+pub async fn install_update(
+    app: AppHandle,
+    backend_state: State<'_, BackendState>,
+) -> Result<(), String> {
+    let updater = app
+        .updater()
+        .map_err(|e| format!("Updater unavailable: {e}"))?;
+
+    let update = updater
+        .check()
+        .await
+        .map_err(|e| format!("Failed to check for update: {e}"))?
+        .ok_or("No update available")?;
+
+    crate::backend::stop_backend(backend_state).await?;
+
+    let mut downloaded: usize = 0;
+    let progress_app = app.clone();
+    update
+        .download_and_install(
+            move |chunk_length, content_length| {
+                downloaded += chunk_length;
+                let _ = progress_app.emit(
+                    "update-progress",
+                    UpdateProgress {
+                        downloaded,
+                        total: content_length,
+                    },
+                );
+            },
+            || {},
+        )
+        .await
+        .map_err(|e| format!("Failed to install update: {e}"))?;
+
+    let _ = app.emit("update-ready", ());
+    Ok(())
+}